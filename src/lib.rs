@@ -1,4 +1,10 @@
-use sha256::digest;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use flate2::read::GzDecoder;
 use once_cell::sync::OnceCell;
 use eyre::ContextCompat;
 use serde::{Serialize, Deserialize};
@@ -18,35 +24,280 @@ const PARAM_NAME: &str = "spec";
 #[derive(Debug, Serialize, Deserialize)]
 struct KeygenParams {
     lookup: Vec<String>,
-    key_name: String
+    key_name: String,
+    #[serde(default)]
+    hash: HashAlgo,
+    #[serde(default)]
+    key_output: KeyOutput,
+    #[serde(default)]
+    input_encoding: InputEncoding,
+    #[serde(default)]
+    filter: Option<FilterSpec>,
+}
+
+/// A single predicate over a JSON-pointed field, AND-ed together with its
+/// siblings in a [`FilterSpec`]. Modeled on subscription-style filters
+/// (e.g. Nostr's `ReqFilter`): an exact-value set plus numeric range bounds
+/// over the same pointed field.
+#[derive(Debug, Serialize, Deserialize)]
+struct FilterCondition {
+    /// JSON pointer to the field this condition is evaluated against.
+    pointer: String,
+    /// Match if the pointed value is one of this set.
+    #[serde(default)]
+    eq: Option<Vec<Value>>,
+    /// Match if the pointed value, read as a number, is >= this bound.
+    #[serde(default)]
+    since: Option<f64>,
+    /// Match if the pointed value, read as a number, is <= this bound.
+    #[serde(default)]
+    until: Option<f64>,
+}
+
+/// Restricts which records get a key generated and emitted. A record must
+/// satisfy every condition (AND) to pass the filter.
+#[derive(Debug, Serialize, Deserialize)]
+struct FilterSpec {
+    conditions: Vec<FilterCondition>,
+}
+
+/// Digest algorithm used to derive the generated key.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum HashAlgo {
+    #[default]
+    Sha256,
+    Sha384,
+    Sha512,
+    Blake3,
+}
+
+/// Where the generated digest ends up on the outgoing record.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum KeyOutput {
+    /// Inject the digest into the JSON body under `key_name` (default).
+    #[default]
+    Field,
+    /// Use the digest as the outgoing record's key instead.
+    RecordKey,
+    /// Do both: inject into the body and use as the record key.
+    Both,
+}
+
+/// How the raw record value needs to be decoded before it can be parsed as
+/// JSON. Some upstreams ship base64-encoded and/or gzip-compressed blobs
+/// instead of plain UTF-8 JSON.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum InputEncoding {
+    /// Record value is already UTF-8 JSON (default).
+    #[default]
+    #[serde(rename = "raw")]
+    Raw,
+    /// Record value is gzip-compressed JSON.
+    #[serde(rename = "gzip")]
+    Gzip,
+    /// Record value is base64-encoded JSON.
+    #[serde(rename = "base64")]
+    Base64,
+    /// Record value is base64-encoded, gzip-compressed JSON.
+    #[serde(rename = "base64+gzip")]
+    Base64Gzip,
+}
+
+/// Inflate a gzip byte stream.
+fn gunzip(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut decoder = GzDecoder::new(bytes);
+    let mut out = Vec::new();
+    decoder.read_to_end(&mut out)?;
+    Ok(out)
+}
+
+/// Decode a record's raw value into a UTF-8 JSON string, undoing whatever
+/// transport encoding the upstream applied.
+fn decode_record_value(record: &Record, encoding: InputEncoding) -> Result<String> {
+    let bytes = record.value.as_ref();
+    let decoded = match encoding {
+        InputEncoding::Raw => bytes.to_vec(),
+        InputEncoding::Gzip => gunzip(bytes)?,
+        InputEncoding::Base64 => STANDARD.decode(bytes)?,
+        InputEncoding::Base64Gzip => gunzip(&STANDARD.decode(bytes)?)?,
+    };
+    Ok(String::from_utf8(decoded)?)
 }
 
 /// Extract json values based on JSON pointer notations:
 ///     [ "/top/one", "/top/two"]
-fn extract_json_fields(data: &str, lookup: &Vec<String>) -> Result<String> {
-    let json:Value = serde_json::from_str(data)?;
-
-    let result = lookup
+/// A segment of `*` matches every element of an array or every value of an
+/// object, expanding to all of the values it resolves to in document order.
+fn extract_json_fields(json: &Value, lookup: &Vec<String>) -> String {
+    lookup
         .iter()
-        .map(|item| json.pointer(item.as_str()))
-        .filter(|v| v.is_some())
-        .map(|value| {
-            let v = value.unwrap();
+        .flat_map(|item| match item.strip_prefix('/') {
+            Some(rest) => {
+                let segments: Vec<&str> = rest.split('/').collect();
+                resolve_pointer(json, &segments)
+            }
+            None if item.is_empty() => vec![json],
+            None => vec![],
+        })
+        .map(|v| {
             if Value::is_string(v) {
                 v.as_str().unwrap().to_owned()
             } else {
-                v.to_string()
+                canonicalize(v).to_string()
             }
         })
         .collect::<Vec<String>>()
-        .join("");
+        .join("")
+}
+
+/// Unescape a single RFC 6901 JSON pointer token: `~1` decodes to `/` and
+/// `~0` decodes to `~`, with `~1` decoded first as the spec requires (so
+/// `~01` correctly decodes to `~1`, not `/`).
+fn unescape_segment(segment: &str) -> Cow<'_, str> {
+    if segment.contains('~') {
+        Cow::Owned(segment.replace("~1", "/").replace("~0", "~"))
+    } else {
+        Cow::Borrowed(segment)
+    }
+}
+
+/// Walk `value` following `segments`, forking into every child on a `*`
+/// segment, and return every value reached. Non-matching branches (a missing
+/// object key, an out-of-range index, indexing into a scalar) are skipped,
+/// exactly like a plain JSON pointer lookup that resolves to nothing.
+fn resolve_pointer<'a>(value: &'a Value, segments: &[&str]) -> Vec<&'a Value> {
+    let Some((segment, rest)) = segments.split_first() else {
+        return vec![value];
+    };
+
+    match *segment {
+        "*" => match value {
+            Value::Object(map) => map.values().flat_map(|v| resolve_pointer(v, rest)).collect(),
+            Value::Array(items) => items.iter().flat_map(|v| resolve_pointer(v, rest)).collect(),
+            _ => vec![],
+        },
+        segment => {
+            let key = unescape_segment(segment);
+            match value {
+                Value::Object(map) => map
+                    .get(key.as_ref())
+                    .map(|v| resolve_pointer(v, rest))
+                    .unwrap_or_default(),
+                Value::Array(items) => key
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| items.get(i))
+                    .map(|v| resolve_pointer(v, rest))
+                    .unwrap_or_default(),
+                _ => vec![],
+            }
+        }
+    }
+}
+
+/// Resolve a single JSON pointer to the first value it reaches, or `None` if
+/// the pointer does not resolve. A `*` segment expands as it does in
+/// `lookup`, and only the first of the values it reaches is returned.
+fn resolve_pointer_value<'a>(json: &'a Value, pointer: &str) -> Option<&'a Value> {
+    let rest = pointer.strip_prefix('/')?;
+    let segments: Vec<&str> = rest.split('/').collect();
+    resolve_pointer(json, &segments).into_iter().next()
+}
+
+/// Read a JSON value as a number, parsing numeric strings as a fallback so
+/// that timestamp-like fields stored as strings still work with `since`/
+/// `until` bounds.
+fn value_as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
+/// Evaluate a single condition against the JSON value it points to.
+fn matches_condition(json: &Value, condition: &FilterCondition) -> bool {
+    let Some(value) = resolve_pointer_value(json, &condition.pointer) else {
+        return false;
+    };
+
+    if let Some(eq) = &condition.eq {
+        if !eq.contains(value) {
+            return false;
+        }
+    }
+    if let Some(since) = condition.since {
+        if value_as_f64(value).is_none_or(|n| n < since) {
+            return false;
+        }
+    }
+    if let Some(until) = condition.until {
+        if value_as_f64(value).is_none_or(|n| n > until) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Evaluate a [`FilterSpec`] against a record's parsed body: every condition
+/// must match (AND).
+fn matches_filter(json: &Value, filter: &FilterSpec) -> bool {
+    filter.conditions.iter().all(|condition| matches_condition(json, condition))
+}
 
-    Ok(result)
+/// Rewrite a JSON value into a canonical form so that semantically equal
+/// documents serialize to the same bytes: object members are emitted in
+/// lexicographically sorted key order, arrays keep their original order,
+/// numbers are rewritten to a single consistent representation, and there is
+/// no insignificant whitespace once serialized.
+fn canonicalize(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let sorted: BTreeMap<String, Value> = map
+                .iter()
+                .map(|(k, v)| (k.clone(), canonicalize(v)))
+                .collect();
+            Value::Object(sorted.into_iter().collect())
+        }
+        Value::Array(items) => Value::Array(items.iter().map(canonicalize).collect()),
+        Value::Number(n) => canonicalize_number(n),
+        v => v.clone(),
+    }
+}
+
+/// Normalize a JSON number so that values which are mathematically equal
+/// (`1`, `1.0`, `1e2` vs `100`) canonicalize to the same representation:
+/// whole numbers are rewritten as integers, everything else as `f64`.
+///
+/// Numbers already stored as an exact `i64`/`u64` (anything written without a
+/// decimal point or exponent) are passed through untouched rather than
+/// routed through `f64`, so integers beyond `f64`'s 2^53 precision limit
+/// (e.g. Snowflake-style IDs) don't collide with their neighbors.
+fn canonicalize_number(n: &serde_json::Number) -> Value {
+    if n.is_i64() || n.is_u64() {
+        return Value::Number(n.clone());
+    }
+    if let Some(f) = n.as_f64() {
+        if f.is_finite() && f.fract() == 0.0 && f >= i64::MIN as f64 && f <= i64::MAX as f64 {
+            return Value::Number((f as i64).into());
+        }
+        if let Some(canonical) = serde_json::Number::from_f64(f) {
+            return Value::Number(canonical);
+        }
+    }
+    Value::Number(n.clone())
 }
 
-/// Ecapsulate sha256::digest in an API.
-fn generate_key(input: String) -> String {
-    digest(input)
+/// Hex-encode a digest computed with the requested algorithm.
+fn generate_key(input: String, algo: HashAlgo) -> String {
+    match algo {
+        HashAlgo::Sha256 => hex::encode(Sha256::digest(input.as_bytes())),
+        HashAlgo::Sha384 => hex::encode(Sha384::digest(input.as_bytes())),
+        HashAlgo::Sha512 => hex::encode(Sha512::digest(input.as_bytes())),
+        HashAlgo::Blake3 => blake3::hash(input.as_bytes()).to_hex().to_string(),
+    }
 }
 
 /// Add keys to a json Value.
@@ -62,25 +313,65 @@ fn add_key(v: &Value, new_key: String, new_value: String) -> Value {
 }
 
 
-/// Generate a new Key field for a JSON record
-fn add_key_to_json_record(record: &Record, spec: &KeygenParams) -> Result<Value> {
-    let record: &str = std::str::from_utf8(record.value.as_ref())?;
-    let key_val = extract_json_fields(record, &spec.lookup)?;
+/// Decode a record's body and parse it as JSON, the shared first step for
+/// both filtering and keying so neither has to re-derive the other's work.
+fn decode_record_json(record: &Record, spec: &KeygenParams) -> Result<Value> {
+    let decoded = decode_record_value(record, spec.input_encoding)?;
+    Ok(serde_json::from_str(&decoded)?)
+}
 
-    let record_value: Value = serde_json::from_str(record)?;
-    let result = add_key(&record_value, 
-        spec.key_name.clone(),  generate_key(key_val));
-    Ok(result)
+/// Compute the dedup digest for an already-decoded record value.
+fn digest_for_value(record_value: &Value, spec: &KeygenParams) -> String {
+    let key_val = extract_json_fields(record_value, &spec.lookup);
+    generate_key(key_val, spec.hash)
 }
 
-#[smartmodule(map)]
-pub fn map(record: &Record) -> Result<(Option<RecordData>, RecordData)> {
-    let key = record.key.clone();
+/// Whether a decoded record passes the spec's filter; with no filter
+/// configured every record passes.
+fn passes_filter(record_value: &Value, spec: &KeygenParams) -> bool {
+    spec.filter.as_ref().is_none_or(|filter| matches_filter(record_value, filter))
+}
+
+/// Place the digest as `spec.key_output` says, returning the outgoing record
+/// key and body. When the digest is not used as the key, the incoming record
+/// key is kept.
+fn apply_key_output(
+    record: &Record,
+    record_value: Value,
+    digest: String,
+    spec: &KeygenParams,
+) -> (Option<RecordData>, Value) {
+    let (body, digest) = match spec.key_output {
+        KeyOutput::Field => (add_key(&record_value, spec.key_name.clone(), digest), None),
+        KeyOutput::RecordKey => (record_value, Some(digest)),
+        KeyOutput::Both => {
+            let body = add_key(&record_value, spec.key_name.clone(), digest.clone());
+            (body, Some(digest))
+        }
+    };
+
+    let key = match digest {
+        Some(digest) => Some(digest.into()),
+        None => record.key.clone(),
+    };
+
+    (key, body)
+}
+
+#[smartmodule(filter_map)]
+pub fn map(record: &Record) -> Result<Option<(Option<RecordData>, RecordData)>> {
     let spec = SPEC.get().wrap_err("spec is not initialized")?;
 
-    let result = add_key_to_json_record(&record, &spec)?;
+    let record_value = decode_record_json(record, spec)?;
 
-    Ok((key, serde_json::to_string(&result)?.into()))
+    if !passes_filter(&record_value, spec) {
+        return Ok(None);
+    }
+
+    let digest = digest_for_value(&record_value, spec);
+    let (key, body) = apply_key_output(record, record_value, digest, spec);
+
+    Ok(Some((key, serde_json::to_string(&body)?.into())))
 }
 
 #[smartmodule(init)]
@@ -122,9 +413,15 @@ mod tests {
         ],
         "pub_date": "Tue, 18 Apr 2023 18:59:04 GMT",
         "last_build_date": "Tue, 20 Apr 2023 15:00:01 GMT",
-        "link": "https://example.com/3343"      
+        "link": "https://example.com/3343"
     }"#;
 
+    /// Test-only convenience that parses `data` before delegating to
+    /// [`extract_json_fields`], so tests can keep passing raw JSON strings.
+    fn extract_json_fields_str(data: &str, lookup: &Vec<String>) -> Result<String> {
+        let json: Value = serde_json::from_str(data)?;
+        Ok(extract_json_fields(&json, lookup))
+    }
 
     #[test]
     fn extract_json_fields_tests() {
@@ -134,20 +431,20 @@ mod tests {
             "/id".to_owned()
         ];
         let result = "373443";
-        assert_eq!(result.to_owned(), extract_json_fields(INPUT, &lookup).unwrap());
+        assert_eq!(result.to_owned(), extract_json_fields_str(INPUT, &lookup).unwrap());
 
         // string
         let lookup = vec![
             "/link".to_owned(),
         ];
         let result = r#"https://example.com/3343"#;
-        assert_eq!(result.to_owned(), extract_json_fields(INPUT, &lookup).unwrap());
+        assert_eq!(result.to_owned(), extract_json_fields_str(INPUT, &lookup).unwrap());
         // nested string
         let lookup = vec![
             "/name/last".to_owned(),
         ];
         let result = r#"Anderson"#;
-        assert_eq!(result.to_owned(), extract_json_fields(INPUT, &lookup).unwrap());
+        assert_eq!(result.to_owned(), extract_json_fields_str(INPUT, &lookup).unwrap());
 
         // multiple strings
         let lookup = vec![
@@ -155,14 +452,14 @@ mod tests {
             "/last_build_date".to_owned(),
         ];
         let result = r#"Tue, 18 Apr 2023 18:59:04 GMTTue, 20 Apr 2023 15:00:01 GMT"#;
-        assert_eq!(result.to_owned(), extract_json_fields(INPUT, &lookup).unwrap());
+        assert_eq!(result.to_owned(), extract_json_fields_str(INPUT, &lookup).unwrap());
 
         // full key-value tree
         let lookup = vec![
             "/name".to_owned(),
         ];
         let result = r#"{"first":"Tom","last":"Anderson"}"#;
-        assert_eq!(result.to_owned(), extract_json_fields(INPUT, &lookup).unwrap());
+        assert_eq!(result.to_owned(), extract_json_fields_str(INPUT, &lookup).unwrap());
 
         // full array tree
         let lookup = vec![
@@ -181,7 +478,7 @@ mod tests {
             }
         ]"#;
         let expected: Value = serde_json::from_str(result).unwrap();
-        assert_eq!(expected.to_string(), extract_json_fields(INPUT, &lookup).unwrap());
+        assert_eq!(expected.to_string(), extract_json_fields_str(INPUT, &lookup).unwrap());
 
         // mixed
         let lookup = vec![
@@ -190,14 +487,127 @@ mod tests {
             "/link".to_owned()
         ];
         let result = r#"Tue, 17 Apr 2023 14:59:44 GMTTue, 18 Apr 2023 15:00:01 GMThttps://example.com/3343"#;
-        assert_eq!(result.to_owned(), extract_json_fields(INPUT, &lookup).unwrap());
+        assert_eq!(result.to_owned(), extract_json_fields_str(INPUT, &lookup).unwrap());
 
         // invalid 
         let lookup = vec![
             "/invalid".to_owned()
         ];
         let result = "";
-        assert_eq!(result.to_owned(), extract_json_fields(INPUT, &lookup).unwrap());
+        assert_eq!(result.to_owned(), extract_json_fields_str(INPUT, &lookup).unwrap());
+    }
+
+    #[test]
+    fn extract_json_fields_wildcard_tests() {
+        // array projection: every link under /items
+        let lookup = vec![
+            "/items/*/link".to_owned()
+        ];
+        let result = "https://example.com/456970https://example.com/3343";
+        assert_eq!(result.to_owned(), extract_json_fields_str(INPUT, &lookup).unwrap());
+
+        // object projection: every value of /name
+        let lookup = vec![
+            "/name/*".to_owned()
+        ];
+        let result = "TomAnderson";
+        assert_eq!(result.to_owned(), extract_json_fields_str(INPUT, &lookup).unwrap());
+
+        // wildcard mixed with a trailing concrete field in a later segment
+        let lookup = vec![
+            "/items/*/pub_date".to_owned(),
+            "/link".to_owned()
+        ];
+        let result = "Tue, 17 Apr 2023 14:59:04 GMTTue, 17 Apr 2023 14:59:44 GMThttps://example.com/3343";
+        assert_eq!(result.to_owned(), extract_json_fields_str(INPUT, &lookup).unwrap());
+
+        // wildcard over a scalar yields nothing, same as a missing pointer
+        let lookup = vec![
+            "/id/*".to_owned()
+        ];
+        let result = "";
+        assert_eq!(result.to_owned(), extract_json_fields_str(INPUT, &lookup).unwrap());
+    }
+
+    #[test]
+    fn extract_json_fields_unescapes_rfc6901_tokens() {
+        let input = r#"{"a/b": "slash-key", "a~b": "tilde-key"}"#;
+
+        let lookup = vec!["/a~1b".to_owned()];
+        assert_eq!("slash-key", extract_json_fields_str(input, &lookup).unwrap());
+
+        let lookup = vec!["/a~0b".to_owned()];
+        assert_eq!("tilde-key", extract_json_fields_str(input, &lookup).unwrap());
+    }
+
+    #[test]
+    fn canonicalize_reorders_object_keys() {
+        let value: Value = serde_json::from_str(
+            r#"{"b": 2, "a": {"y": 2, "x": 1}, "c": [3, 1, 2]}"#
+        ).unwrap();
+        let expected: Value = serde_json::from_str(
+            r#"{"a": {"x": 1, "y": 2}, "b": 2, "c": [3, 1, 2]}"#
+        ).unwrap();
+        assert_eq!(canonicalize(&value).to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn canonicalize_normalizes_number_representation() {
+        let int_value: Value = serde_json::from_str(r#"{"x": 1}"#).unwrap();
+        let float_value: Value = serde_json::from_str(r#"{"x": 1.0}"#).unwrap();
+        assert_eq!(canonicalize(&int_value).to_string(), canonicalize(&float_value).to_string());
+
+        let exponent_value: Value = serde_json::from_str(r#"{"x": 1e2}"#).unwrap();
+        let hundred_value: Value = serde_json::from_str(r#"{"x": 100}"#).unwrap();
+        assert_eq!(canonicalize(&exponent_value).to_string(), canonicalize(&hundred_value).to_string());
+    }
+
+    #[test]
+    fn canonicalize_preserves_large_integers_beyond_f64_precision() {
+        // Snowflake-style dedup IDs straddling f64's 2^53 precision limit
+        // must not collide after canonicalization.
+        let first: Value = serde_json::from_str(r#"{"x": 2000000000000000001}"#).unwrap();
+        let second: Value = serde_json::from_str(r#"{"x": 2000000000000000002}"#).unwrap();
+        assert_ne!(canonicalize(&first).to_string(), canonicalize(&second).to_string());
+        assert_ne!(
+            generate_key(canonicalize(&first).to_string(), HashAlgo::Sha256),
+            generate_key(canonicalize(&second).to_string(), HashAlgo::Sha256)
+        );
+    }
+
+    #[test]
+    fn extract_json_fields_is_stable_across_key_order() {
+        let reordered = r#"{
+            "name": {"last": "Anderson", "first": "Tom"},
+            "id": 373443,
+            "items": [
+                {
+                    "link": "https://example.com/456970",
+                    "pub_date": "Tue, 17 Apr 2023 14:59:04 GMT",
+                    "last_build_date": "Tue, 18 Apr 2023 15:00:01 GMT"
+                },
+                {
+                    "link": "https://example.com/3343",
+                    "pub_date": "Tue, 17 Apr 2023 14:59:44 GMT",
+                    "last_build_date": "Tue, 18 Apr 2023 15:00:01 GMT"
+                }
+            ],
+            "pub_date": "Tue, 18 Apr 2023 18:59:04 GMT",
+            "last_build_date": "Tue, 20 Apr 2023 15:00:01 GMT",
+            "link": "https://example.com/3343"
+        }"#;
+
+        let lookup = vec!["/name".to_owned()];
+        assert_eq!(
+            extract_json_fields_str(INPUT, &lookup).unwrap(),
+            extract_json_fields_str(reordered, &lookup).unwrap()
+        );
+
+        let lookup = vec!["/items".to_owned()];
+        assert_eq!(
+            generate_key(extract_json_fields_str(INPUT, &lookup).unwrap(), HashAlgo::Sha256),
+            generate_key(extract_json_fields_str(reordered, &lookup).unwrap(), HashAlgo::Sha256)
+        );
     }
 
     #[test]
@@ -205,15 +615,33 @@ mod tests {
         // simple
         let input = "Tue, 17 Apr 2023 14:59:04 GMT";
         assert_eq!(
-            generate_key(input.to_owned()),
+            generate_key(input.to_owned(), HashAlgo::Sha256),
             "ba021aa33e0ba9557bae32efc690cc1c162aa6c2a0c62cb8527dc8fe7d5ca8d7");
 
         let input = r#"["Sara","Alex","Jack"]"#;
         assert_eq!(
-            generate_key(input.to_owned()),
+            generate_key(input.to_owned(), HashAlgo::Sha256),
             "0c5507584b9b6c163335cd626fca364a3a34835a71383111b492a2249a64535f");
     }
 
+    #[test]
+    fn generate_key_algo_tests() {
+        // known vectors for "hello" across the supported algorithms
+        let input = "hello";
+        assert_eq!(
+            generate_key(input.to_owned(), HashAlgo::Sha256),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+        assert_eq!(
+            generate_key(input.to_owned(), HashAlgo::Sha384),
+            "59e1748777448c69de6b800d7a33bbfb9ff1b463e44354c3553bcdb9c666fa90125a3c79f90397bdf5f6a13de828684f");
+        assert_eq!(
+            generate_key(input.to_owned(), HashAlgo::Sha512),
+            "9b71d224bd62f3785d96d46ad3ea3d73319bfbc2890caadae2dff72519673ca72323c3d99ba5c11d7c7acc6e14b8c5da0c4663475c2e5c3adef46f73bcdec043");
+        assert_eq!(
+            generate_key(input.to_owned(), HashAlgo::Blake3),
+            "ea8f163db38682925e4491c5e58d4bb3506ef8c14eb78a86e908c5624a67200f");
+    }
+
     #[test]
     fn add_key_tests() {
         let input = r#"{
@@ -252,21 +680,282 @@ mod tests {
             "link": "http://www.example.com",
             "pub_date": "Mon, 17 Apr 2023 16:08:23 GMT",
             "title": "My Json Object Title"
-        }"#;        
+        }"#;
         let spec = KeygenParams {
             lookup: vec![
-                "pub_date".to_owned(), 
+                "pub_date".to_owned(),
                 "last_build_date".to_owned()
             ],
-            key_name: "dedup_key".to_owned()
+            key_name: "dedup_key".to_owned(),
+            hash: HashAlgo::Sha256,
+            key_output: KeyOutput::Field,
+            input_encoding: InputEncoding::Raw,
+            filter: None
         };
 
         let record = Record::new(input);
-        let result = add_key_to_json_record(&record, &spec).unwrap();
+        let record_value = decode_record_json(&record, &spec).unwrap();
+        let digest = digest_for_value(&record_value, &spec);
+        let result = add_key(&record_value, spec.key_name.clone(), digest);
 
         let expected_value:Value = serde_json::from_str(expected).unwrap();
         assert_eq!(result, expected_value);
 
     }
 
+    #[test]
+    fn map_key_output_tests() {
+        let input = r#"{
+            "last_build_date": "Tue, 18 Apr 2023 15:00:01 GMT",
+            "pub_date": "Mon, 17 Apr 2023 16:08:23 GMT"
+        }"#;
+        let spec = KeygenParams {
+            lookup: vec![
+                "/pub_date".to_owned(),
+                "/last_build_date".to_owned()
+            ],
+            key_name: "dedup_key".to_owned(),
+            hash: HashAlgo::Sha256,
+            key_output: KeyOutput::Field,
+            input_encoding: InputEncoding::Raw,
+            filter: None
+        };
+        let digest = generate_key(
+            extract_json_fields_str(input, &spec.lookup).unwrap(),
+            spec.hash
+        );
+
+        // field: body gains the key, record key is untouched
+        let record = Record::new_key_value("original-key", input);
+        let record_value = decode_record_json(&record, &spec).unwrap();
+        let d = digest_for_value(&record_value, &spec);
+        assert_eq!(d, digest);
+        let (key, body) = apply_key_output(&record, record_value, d, &spec);
+        assert_eq!(body["dedup_key"], Value::String(digest.clone()));
+        assert_eq!(key, record.key.clone());
+
+        // record_key: digest becomes the record key, body is untouched
+        let spec = KeygenParams { key_output: KeyOutput::RecordKey, ..spec };
+        let record = Record::new_key_value("original-key", input);
+        let record_value = decode_record_json(&record, &spec).unwrap();
+        let d = digest_for_value(&record_value, &spec);
+        assert_eq!(d, digest);
+        let (key, body) = apply_key_output(&record, record_value, d, &spec);
+        assert!(body.get("dedup_key").is_none());
+        assert_eq!(key, Some(RecordData::from(digest.clone())));
+
+        // both: digest lands in the body and becomes the record key
+        let spec = KeygenParams { key_output: KeyOutput::Both, ..spec };
+        let record = Record::new_key_value("original-key", input);
+        let record_value = decode_record_json(&record, &spec).unwrap();
+        let d = digest_for_value(&record_value, &spec);
+        let (key, body) = apply_key_output(&record, record_value, d.clone(), &spec);
+        assert_eq!(d, digest);
+        assert_eq!(body["dedup_key"], Value::String(digest.clone()));
+        assert_eq!(key, Some(RecordData::from(digest)));
+    }
+
+    /// Gzip-compress and base64-encode `data`, mirroring the encoding a
+    /// `base64+gzip` upstream would apply before publishing a record.
+    fn gzip_base64(data: &str) -> String {
+        use std::io::Write;
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data.as_bytes()).unwrap();
+        STANDARD.encode(encoder.finish().unwrap())
+    }
+
+    #[test]
+    fn decode_record_value_tests() {
+        let input = r#"{
+            "pub_date": "Mon, 17 Apr 2023 16:08:23 GMT",
+            "last_build_date": "Tue, 18 Apr 2023 15:00:01 GMT"
+        }"#;
+
+        // raw: no transformation
+        let record = Record::new(input);
+        assert_eq!(decode_record_value(&record, InputEncoding::Raw).unwrap(), input);
+
+        // base64: decoded back to the original text
+        let record = Record::new(STANDARD.encode(input));
+        assert_eq!(decode_record_value(&record, InputEncoding::Base64).unwrap(), input);
+
+        // base64+gzip: inflated and decoded back to the original text
+        let record = Record::new(gzip_base64(input));
+        assert_eq!(decode_record_value(&record, InputEncoding::Base64Gzip).unwrap(), input);
+    }
+
+    #[test]
+    fn decode_record_json_gzip_base64_matches_plaintext() {
+        let input = r#"{
+            "pub_date": "Mon, 17 Apr 2023 16:08:23 GMT",
+            "last_build_date": "Tue, 18 Apr 2023 15:00:01 GMT"
+        }"#;
+        let spec = KeygenParams {
+            lookup: vec![
+                "/pub_date".to_owned(),
+                "/last_build_date".to_owned()
+            ],
+            key_name: "dedup_key".to_owned(),
+            hash: HashAlgo::Sha256,
+            key_output: KeyOutput::Field,
+            input_encoding: InputEncoding::Raw,
+            filter: None
+        };
+
+        let plain_record = Record::new(input);
+        let plain_value = decode_record_json(&plain_record, &spec).unwrap();
+        let plain_digest = digest_for_value(&plain_value, &spec);
+
+        let spec = KeygenParams { input_encoding: InputEncoding::Base64Gzip, ..spec };
+        let compressed_record = Record::new(gzip_base64(input));
+        let compressed_value = decode_record_json(&compressed_record, &spec).unwrap();
+        let compressed_digest = digest_for_value(&compressed_value, &spec);
+
+        assert_eq!(compressed_digest, plain_digest);
+        assert_eq!(compressed_value, plain_value);
+    }
+
+    #[test]
+    fn matches_filter_eq_tests() {
+        let json: Value = serde_json::from_str(INPUT).unwrap();
+
+        // value is in the set: passes
+        let filter = FilterSpec {
+            conditions: vec![FilterCondition {
+                pointer: "/name/last".to_owned(),
+                eq: Some(vec![Value::String("Anderson".to_owned()), Value::String("Smith".to_owned())]),
+                since: None,
+                until: None,
+            }],
+        };
+        assert!(matches_filter(&json, &filter));
+
+        // value is not in the set: fails
+        let filter = FilterSpec {
+            conditions: vec![FilterCondition {
+                pointer: "/name/last".to_owned(),
+                eq: Some(vec![Value::String("Smith".to_owned())]),
+                since: None,
+                until: None,
+            }],
+        };
+        assert!(!matches_filter(&json, &filter));
+
+        // pointer does not resolve: fails
+        let filter = FilterSpec {
+            conditions: vec![FilterCondition {
+                pointer: "/missing".to_owned(),
+                eq: Some(vec![Value::String("Anderson".to_owned())]),
+                since: None,
+                until: None,
+            }],
+        };
+        assert!(!matches_filter(&json, &filter));
+    }
+
+    #[test]
+    fn matches_filter_since_until_tests() {
+        let json: Value = serde_json::from_str(INPUT).unwrap();
+
+        // id (373443) within bounds: passes
+        let filter = FilterSpec {
+            conditions: vec![FilterCondition {
+                pointer: "/id".to_owned(),
+                eq: None,
+                since: Some(100_000.0),
+                until: Some(400_000.0),
+            }],
+        };
+        assert!(matches_filter(&json, &filter));
+
+        // id below the `since` bound: fails
+        let filter = FilterSpec {
+            conditions: vec![FilterCondition {
+                pointer: "/id".to_owned(),
+                eq: None,
+                since: Some(500_000.0),
+                until: None,
+            }],
+        };
+        assert!(!matches_filter(&json, &filter));
+
+        // id above the `until` bound: fails
+        let filter = FilterSpec {
+            conditions: vec![FilterCondition {
+                pointer: "/id".to_owned(),
+                eq: None,
+                since: None,
+                until: Some(1_000.0),
+            }],
+        };
+        assert!(!matches_filter(&json, &filter));
+    }
+
+    #[test]
+    fn matches_filter_and_across_conditions_tests() {
+        let json: Value = serde_json::from_str(INPUT).unwrap();
+
+        // both conditions hold: passes
+        let filter = FilterSpec {
+            conditions: vec![
+                FilterCondition {
+                    pointer: "/name/last".to_owned(),
+                    eq: Some(vec![Value::String("Anderson".to_owned())]),
+                    since: None,
+                    until: None,
+                },
+                FilterCondition {
+                    pointer: "/id".to_owned(),
+                    eq: None,
+                    since: Some(100_000.0),
+                    until: None,
+                },
+            ],
+        };
+        assert!(matches_filter(&json, &filter));
+
+        // one condition fails: the whole filter fails
+        let filter = FilterSpec {
+            conditions: vec![
+                FilterCondition {
+                    pointer: "/name/last".to_owned(),
+                    eq: Some(vec![Value::String("Anderson".to_owned())]),
+                    since: None,
+                    until: None,
+                },
+                FilterCondition {
+                    pointer: "/id".to_owned(),
+                    eq: None,
+                    since: Some(500_000.0),
+                    until: None,
+                },
+            ],
+        };
+        assert!(!matches_filter(&json, &filter));
+    }
+
+    #[test]
+    fn map_without_filter_passes_every_record() {
+        let input = r#"{
+            "pub_date": "Mon, 17 Apr 2023 16:08:23 GMT",
+            "last_build_date": "Tue, 18 Apr 2023 15:00:01 GMT"
+        }"#;
+        let spec = KeygenParams {
+            lookup: vec!["/pub_date".to_owned()],
+            key_name: "dedup_key".to_owned(),
+            hash: HashAlgo::Sha256,
+            key_output: KeyOutput::Field,
+            input_encoding: InputEncoding::Raw,
+            filter: None,
+        };
+
+        // pass-through case: no filter configured, the record is always kept
+        let result = decode_record_json(&Record::new(input), &spec);
+        assert!(result.is_ok());
+        assert!(passes_filter(&result.unwrap(), &spec));
+    }
+
 }
\ No newline at end of file